@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use azure_core::time::Duration;
+use azure_storage::prelude::LeaseId;
+use azure_storage_blobs::prelude::ContainerClient;
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum LeaseSubCommands {
+    /// Acquire a new lease, printing the granted lease id
+    Acquire {
+        /// lease duration in seconds (15-60), or -1 for an infinite lease
+        #[clap(long, default_value = "-1", allow_hyphen_values = true)]
+        duration: i64,
+
+        /// propose a specific lease id instead of letting the service generate one
+        #[clap(long)]
+        proposed_lease_id: Option<LeaseId>,
+    },
+    /// Renew an existing lease
+    Renew {
+        /// the lease id to renew
+        lease_id: LeaseId,
+    },
+    /// Change the id of an existing lease
+    Change {
+        /// the current lease id
+        lease_id: LeaseId,
+
+        /// the proposed replacement lease id
+        proposed_lease_id: LeaseId,
+    },
+    /// Release an existing lease
+    Release {
+        /// the lease id to release
+        lease_id: LeaseId,
+    },
+    /// Break a lease, leaving it in the broken state until it expires
+    Break,
+}
+
+/// Map the `--duration` flag onto the lease duration the service accepts: a
+/// finite lease must be 15-60 seconds, and an infinite lease is encoded as -1.
+fn lease_duration(duration: i64) -> Result<Duration> {
+    if duration < 0 {
+        Ok(Duration::seconds(-1))
+    } else if (15..=60).contains(&duration) {
+        Ok(Duration::seconds(duration))
+    } else {
+        bail!("lease duration must be between 15 and 60 seconds, or -1 for an infinite lease");
+    }
+}
+
+/// Manage the lease on a container, or on a blob within it when `blob_name` is set.
+pub async fn lease_commands(
+    container_client: &ContainerClient,
+    blob_name: Option<String>,
+    subcommand: LeaseSubCommands,
+) -> Result<()> {
+    if let Some(blob_name) = blob_name {
+        let client = container_client.blob_client(blob_name);
+        match subcommand {
+            LeaseSubCommands::Acquire {
+                duration,
+                proposed_lease_id,
+            } => {
+                let mut builder = client.acquire_lease(lease_duration(duration)?);
+                if let Some(proposed_lease_id) = proposed_lease_id {
+                    builder = builder.proposed_lease_id(proposed_lease_id);
+                }
+                println!("{}", builder.await?.lease_id);
+            }
+            LeaseSubCommands::Renew { lease_id } => {
+                println!("{}", client.blob_lease_client(lease_id).renew().await?.lease_id);
+            }
+            LeaseSubCommands::Change {
+                lease_id,
+                proposed_lease_id,
+            } => {
+                let response = client
+                    .blob_lease_client(lease_id)
+                    .change(proposed_lease_id)
+                    .await?;
+                println!("{}", response.lease_id);
+            }
+            LeaseSubCommands::Release { lease_id } => {
+                client.blob_lease_client(lease_id).release().await?;
+            }
+            LeaseSubCommands::Break => {
+                client.break_lease().await?;
+            }
+        }
+    } else {
+        match subcommand {
+            LeaseSubCommands::Acquire {
+                duration,
+                proposed_lease_id,
+            } => {
+                let mut builder = container_client.acquire_lease(lease_duration(duration)?);
+                if let Some(proposed_lease_id) = proposed_lease_id {
+                    builder = builder.proposed_lease_id(proposed_lease_id);
+                }
+                println!("{}", builder.await?.lease_id);
+            }
+            LeaseSubCommands::Renew { lease_id } => {
+                let response = container_client.container_lease_client(lease_id).renew().await?;
+                println!("{}", response.lease_id);
+            }
+            LeaseSubCommands::Change {
+                lease_id,
+                proposed_lease_id,
+            } => {
+                let response = container_client
+                    .container_lease_client(lease_id)
+                    .change(proposed_lease_id)
+                    .await?;
+                println!("{}", response.lease_id);
+            }
+            LeaseSubCommands::Release { lease_id } => {
+                container_client.container_lease_client(lease_id).release().await?;
+            }
+            LeaseSubCommands::Break => {
+                container_client.break_lease().await?;
+            }
+        }
+    }
+    Ok(())
+}
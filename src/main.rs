@@ -11,6 +11,7 @@ mod blob;
 mod macros;
 mod container;
 mod datalake;
+mod lease;
 mod queue;
 mod tables;
 mod utils;
@@ -19,19 +20,25 @@ use self::{
     account::{account_commands, AccountSubCommands},
     container::{container_commands, ContainerSubCommands},
     datalake::{datalake_commands, DatalakeSubCommands},
+    lease::{lease_commands, LeaseSubCommands},
     queue::{queues_commands, QueuesSubCommands},
     tables::{table_commands, TableSubCommands},
 };
-use anyhow::Result;
-use azure_core::auth::Secret;
+use azure_storage::prelude::LeaseId;
+use anyhow::{bail, Context, Result};
+use azure_core::auth::{Secret, TokenCredential};
 use azure_data_tables::clients::TableServiceClient;
-use azure_identity::DefaultAzureCredential;
-use azure_storage::prelude::StorageCredentials;
+use azure_identity::{
+    AzureCliCredential, DefaultAzureCredential, EnvironmentCredential,
+    ImdsManagedIdentityCredential, TokenCredentialOptions, WorkloadIdentityCredential,
+};
+use azure_storage::{prelude::StorageCredentials, CloudLocation, ConnectionString};
 use azure_storage_blobs::prelude::BlobServiceClient;
 use azure_storage_datalake::prelude::DataLakeClient;
 use azure_storage_queues::prelude::QueueServiceClient;
-use clap::{Command, CommandFactory, Parser, Subcommand};
+use clap::{Command, CommandFactory, Parser, Subcommand, ValueEnum};
 use std::{cmp::min, sync::Arc};
+use tracing::info_span;
 
 #[derive(Parser)]
 #[command(
@@ -41,9 +48,10 @@ use std::{cmp::min, sync::Arc};
     disable_help_subcommand = true
 )]
 struct Args {
-    /// storage account name.  Set the environment variable STORAGE_ACCOUNT to set a default
-    #[clap(long, env = "STORAGE_ACCOUNT", hide_env_values = true)]
-    account: String,
+    /// storage account name.  Set the environment variable STORAGE_ACCOUNT to set a default.
+    /// Not required when the account name is supplied via `--connection-string`
+    #[clap(long, env = "STORAGE_ACCOUNT", hide_env_values = true, required_unless_present = "connection_string")]
+    account: Option<String>,
 
     #[command(subcommand)]
     subcommand: SubCommands,
@@ -51,8 +59,147 @@ struct Args {
     /// storage account access key.  If not set, authentication will be done via
     /// Azure Entra Id using the `DefaultAzureCredential`
     /// (see https://docs.rs/azure_identity/latest/azure_identity/struct.DefaultAzureCredential.html)
-    #[clap(long, env = "STORAGE_ACCESS_KEY", hide_env_values = true)]
+    /// A SAS token or connection string is used instead when given, and `--auth-method` can select another credential
+    #[clap(long, env = "STORAGE_ACCESS_KEY", hide_env_values = true, conflicts_with_all = ["sas_token", "connection_string"])]
     access_key: Option<Secret>,
+
+    /// shared access signature (SAS) token used to authenticate requests
+    #[clap(long, env = "STORAGE_SAS_TOKEN", hide_env_values = true, conflicts_with_all = ["access_key", "connection_string"])]
+    sas_token: Option<Secret>,
+
+    /// connection string of the form `DefaultEndpointsProtocol=...;AccountName=...;AccountKey=...;`.
+    /// The credentials are taken from the string, and so is the account name unless `--account` is also set
+    #[clap(long, env = "STORAGE_CONNECTION_STRING", hide_env_values = true, conflicts_with_all = ["access_key", "sas_token"])]
+    connection_string: Option<Secret>,
+
+    /// custom service endpoint to use instead of the public `*.core.windows.net` hosts
+    /// (e.g. a sovereign cloud or a reverse proxy)
+    #[clap(long, env = "STORAGE_ENDPOINT")]
+    endpoint: Option<String>,
+
+    /// target a local Azurite storage emulator on the well-known loopback ports
+    /// (blob 10000, queue 10001, table 10002)
+    #[clap(long, conflicts_with = "endpoint")]
+    emulator: bool,
+
+    /// export distributed traces to an OpenTelemetry OTLP endpoint
+    /// (e.g. `http://localhost:4317`) in addition to logging to stderr
+    #[clap(long, env = "OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
+    /// token credential to use when no access key, SAS token, or connection string is supplied
+    #[clap(long, value_enum, default_value_t = AuthMethod::Default)]
+    auth_method: AuthMethod,
+
+    /// client id of a user-assigned identity.  Used with
+    /// `--auth-method managed-identity` and `--auth-method workload-identity`;
+    /// ignored for the `default`, `cli`, and `environment` methods
+    #[clap(long, env = "AZURE_CLIENT_ID", hide_env_values = true)]
+    client_id: Option<String>,
+}
+
+/// The Azure Entra Id credential mechanism to authenticate with.
+#[derive(Clone, Copy, ValueEnum)]
+enum AuthMethod {
+    /// chain the available credentials via `DefaultAzureCredential`
+    Default,
+    /// use the identity logged in via the `az` CLI
+    Cli,
+    /// use the managed identity assigned to the host
+    ManagedIdentity,
+    /// use a Kubernetes federated token (workload identity)
+    WorkloadIdentity,
+    /// read credentials from the `AZURE_*` environment variables
+    Environment,
+}
+
+/// Build the requested token credential.  The `workload-identity` and
+/// `environment` methods fail here with a clear error when their configuration
+/// is missing from the environment; the `cli` and `managed-identity` credentials
+/// are built without such a check and only fail once a token is requested.
+fn token_credential(
+    method: AuthMethod,
+    client_id: Option<String>,
+) -> Result<Arc<dyn TokenCredential>> {
+    let credential: Arc<dyn TokenCredential> = match method {
+        AuthMethod::Default => Arc::new(DefaultAzureCredential::default()),
+        AuthMethod::Cli => Arc::new(AzureCliCredential::new()),
+        AuthMethod::ManagedIdentity => {
+            let mut credential = ImdsManagedIdentityCredential::default();
+            if let Some(client_id) = client_id {
+                credential = credential.with_client_id(client_id);
+            }
+            Arc::new(credential)
+        }
+        AuthMethod::WorkloadIdentity => {
+            let mut credential = WorkloadIdentityCredential::create(TokenCredentialOptions::default())
+                .context("workload identity credential is not available in this environment")?;
+            if let Some(client_id) = client_id {
+                credential = credential.with_client_id(client_id);
+            }
+            Arc::new(credential)
+        }
+        AuthMethod::Environment => Arc::new(
+            EnvironmentCredential::create(TokenCredentialOptions::default())
+                .context("environment credential is not available (missing AZURE_* variables)")?,
+        ),
+    };
+    Ok(credential)
+}
+
+/// Configure the global `tracing` subscriber.
+///
+/// Log output always goes to stderr, filtered by the `RUST_LOG` environment
+/// variable.  When an OTLP endpoint is supplied, an OpenTelemetry pipeline is
+/// installed alongside it so each storage request is emitted as a span.
+fn init_tracing(otlp_endpoint: Option<&str>) -> Result<()> {
+    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr));
+
+    if let Some(endpoint) = otlp_endpoint {
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+        registry
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .init();
+    } else {
+        registry.init();
+    }
+
+    Ok(())
+}
+
+/// Resolve the [`CloudLocation`] a service client should be built with.
+///
+/// Returns `None` when the public Azure endpoints should be used, in which case
+/// the plain `*ServiceClient::new` constructor is sufficient.
+fn cloud_location(
+    account: &str,
+    endpoint: Option<&str>,
+    emulator: bool,
+    emulator_port: u16,
+) -> Option<CloudLocation> {
+    if emulator {
+        Some(CloudLocation::Emulator {
+            address: "127.0.0.1".to_string(),
+            port: emulator_port,
+        })
+    } else {
+        endpoint.map(|uri| CloudLocation::Custom {
+            account: account.to_string(),
+            uri: uri.to_string(),
+        })
+    }
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -70,6 +217,23 @@ enum SubCommands {
 
         /// container name
         container_name: String,
+
+        /// lease id to pass through to mutating operations (uploads, overwrites,
+        /// deletes) so they succeed against a leased blob or container
+        #[clap(long)]
+        lease_id: Option<LeaseId>,
+    },
+    /// Acquire and manage leases on a container or blob
+    Lease {
+        #[clap(subcommand)]
+        subcommand: LeaseSubCommands,
+
+        /// container name
+        container_name: String,
+
+        /// blob name; when omitted the lease targets the container itself
+        #[clap(long)]
+        blob_name: Option<String>,
     },
     /// Interact with storage queues
     Queues {
@@ -125,18 +289,60 @@ fn build_readme(cmd: &mut Command, mut names: Vec<String>) -> String {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
     let Args {
         access_key,
         account,
+        sas_token,
+        connection_string,
+        endpoint,
+        emulator,
+        otlp_endpoint,
+        auth_method,
+        client_id,
         subcommand,
     } = Args::parse();
 
-    let storage_credentials = match access_key {
-        Some(access_key) => StorageCredentials::access_key(&account, access_key),
-        None => StorageCredentials::token_credential(Arc::new(DefaultAzureCredential::default())),
+    let otlp_enabled = otlp_endpoint.is_some();
+    init_tracing(otlp_endpoint.as_deref())?;
+
+    // precedence: explicit flag (SAS token) > connection string > access key > token credential
+    let mut account = account;
+    // per-service endpoints carried by a connection string (e.g. the Azurite
+    // form, where blob/queue/table live on distinct ports).  An explicit
+    // --endpoint still wins over these on a per-subcommand basis below.
+    let mut cs_blob_endpoint: Option<String> = None;
+    let mut cs_queue_endpoint: Option<String> = None;
+    let mut cs_table_endpoint: Option<String> = None;
+    let storage_credentials = if let Some(sas_token) = sas_token {
+        StorageCredentials::sas_token(sas_token.secret())?
+    } else if let Some(connection_string) = connection_string {
+        let connection_string = ConnectionString::new(connection_string.secret())?;
+        if let Some(account_name) = connection_string.account_name {
+            account.get_or_insert_with(|| account_name.to_string());
+        }
+        cs_blob_endpoint = connection_string.blob_endpoint.map(str::to_string);
+        cs_queue_endpoint = connection_string.queue_endpoint.map(str::to_string);
+        cs_table_endpoint = connection_string.table_endpoint.map(str::to_string);
+        match (connection_string.account_key, connection_string.sas) {
+            (Some(account_key), _) => {
+                let account = account.as_deref().context("connection string is missing an account name")?;
+                StorageCredentials::access_key(account, Secret::new(account_key.to_string()))
+            }
+            (None, Some(sas)) => StorageCredentials::sas_token(sas)?,
+            (None, None) => bail!("connection string does not contain an account key or SAS token"),
+        }
+    } else if let Some(access_key) = access_key {
+        let account = account.as_deref().context("storage account name is required")?;
+        StorageCredentials::access_key(account, access_key)
+    } else {
+        StorageCredentials::token_credential(token_credential(auth_method, client_id)?)
     };
 
+    let account = account.context("storage account name is required")?;
+
+    // root span so every storage request for this invocation shares a trace
+    let _root = info_span!("azs", account = %account).entered();
+
     match subcommand {
         SubCommands::Readme => {
             let mut cmd = Args::command();
@@ -156,30 +362,95 @@ async fn main() -> Result<()> {
             print!("{readme}");
         }
         SubCommands::Account { subcommand } => {
-            let service_client = BlobServiceClient::new(&account, storage_credentials);
+            let _span = info_span!("account", account = %account).entered();
+            let endpoint = endpoint.as_deref().or(cs_blob_endpoint.as_deref());
+            let service_client = match cloud_location(&account, endpoint, emulator, 10000) {
+                Some(cloud) => azure_storage_blobs::prelude::ClientBuilder::with_location(
+                    cloud,
+                    storage_credentials,
+                )
+                .blob_service_client(),
+                None => BlobServiceClient::new(&account, storage_credentials),
+            };
             account_commands(&service_client, subcommand).await?;
         }
         SubCommands::Container {
             subcommand,
             container_name,
+            lease_id,
+        } => {
+            let _span = info_span!("container", account = %account, container = %container_name).entered();
+            let endpoint = endpoint.as_deref().or(cs_blob_endpoint.as_deref());
+            let service_client = match cloud_location(&account, endpoint, emulator, 10000) {
+                Some(cloud) => azure_storage_blobs::prelude::ClientBuilder::with_location(
+                    cloud,
+                    storage_credentials,
+                )
+                .blob_service_client(),
+                None => BlobServiceClient::new(&account, storage_credentials),
+            };
+            let container_client = service_client.container_client(container_name);
+            container_commands(&container_client, subcommand, lease_id).await?;
+        }
+        SubCommands::Lease {
+            subcommand,
+            container_name,
+            blob_name,
         } => {
-            let service_client = BlobServiceClient::new(&account, storage_credentials);
+            let _span = info_span!("lease", account = %account, container = %container_name).entered();
+            let endpoint = endpoint.as_deref().or(cs_blob_endpoint.as_deref());
+            let service_client = match cloud_location(&account, endpoint, emulator, 10000) {
+                Some(cloud) => azure_storage_blobs::prelude::ClientBuilder::with_location(
+                    cloud,
+                    storage_credentials,
+                )
+                .blob_service_client(),
+                None => BlobServiceClient::new(&account, storage_credentials),
+            };
             let container_client = service_client.container_client(container_name);
-            container_commands(&container_client, subcommand).await?;
+            lease_commands(&container_client, blob_name, subcommand).await?;
         }
         SubCommands::Queues { subcommand } => {
-            let service_client = QueueServiceClient::new(&account, storage_credentials);
+            let _span = info_span!("queues", account = %account).entered();
+            let endpoint = endpoint.as_deref().or(cs_queue_endpoint.as_deref());
+            let service_client = match cloud_location(&account, endpoint, emulator, 10001) {
+                Some(cloud) => azure_storage_queues::prelude::ClientBuilder::with_location(
+                    cloud,
+                    storage_credentials,
+                )
+                .queue_service_client(),
+                None => QueueServiceClient::new(&account, storage_credentials),
+            };
             queues_commands(&service_client, subcommand).await?;
         }
         SubCommands::Datalake { subcommand } => {
-            let service_client = DataLakeClient::new(&account, storage_credentials);
+            let _span = info_span!("datalake", account = %account).entered();
+            let endpoint = endpoint.as_deref().or(cs_blob_endpoint.as_deref());
+            let service_client = match cloud_location(&account, endpoint, emulator, 10000) {
+                Some(cloud) => DataLakeClient::new_with_location(cloud, storage_credentials),
+                None => DataLakeClient::new(&account, storage_credentials),
+            };
             datalake_commands(&service_client, subcommand).await?;
         }
         SubCommands::Tables { subcommand } => {
-            let table_client = TableServiceClient::new(&account, storage_credentials);
+            let _span = info_span!("tables", account = %account).entered();
+            let endpoint = endpoint.as_deref().or(cs_table_endpoint.as_deref());
+            let table_client = match cloud_location(&account, endpoint, emulator, 10002) {
+                Some(cloud) => azure_data_tables::clients::TableServiceClientBuilder::with_location(
+                    cloud,
+                    storage_credentials,
+                )
+                .build(),
+                None => TableServiceClient::new(&account, storage_credentials),
+            };
             table_commands(&table_client, subcommand).await?;
         }
     }
 
+    // flush any buffered spans to the OTLP collector before exiting
+    if otlp_enabled {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+
     Ok(())
 }